@@ -4,7 +4,7 @@ use std::{
     path::Path,
 };
 
-use scala_native_demangle::{self, DemanglingConfig};
+use scala_native_demangle::{self, DemanglingConfig, SimplificationRules};
 
 use clap::{Parser, Subcommand};
 
@@ -21,24 +21,56 @@ enum Commands {
     /// Process a file of mangled identifiers, outputting results inline, separating mangled/unmangled names via ` = `
     File {
         name: String,
+        #[arg(long)]
         debug: bool,
+        #[arg(long)]
+        simplify_rules: Option<String>,
     },
     Id {
         name: String,
         #[arg(long)]
         debug: bool,
+        #[arg(long)]
+        simplify_rules: Option<String>,
+    },
+    /// Demangle `_S…` symbols embedded anywhere in stdin (stack traces, linker errors,
+    /// `nm`/`objdump` output, ...), passing everything else through untouched
+    Filter {
+        #[arg(long)]
+        debug: bool,
+        /// Extra name-simplification rules to apply on top of the built-in
+        /// ones, one per line (`alias <name> <replacement>` or `strip-prefix <prefix>`)
+        #[arg(long)]
+        simplify_rules: Option<String>,
     },
 }
 
+fn load_simplification_rules(path: &Option<String>) -> SimplificationRules {
+    match path {
+        Some(path) => SimplificationRules::load_from_file(Path::new(path))
+            .unwrap_or_else(|e| panic!("failed to load simplification rules from {path}: {e}")),
+        None => SimplificationRules::default(),
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::File { name, debug } => {
+        Commands::File {
+            name,
+            debug,
+            simplify_rules,
+        } => {
+            let config = DemanglingConfig {
+                debug: *debug,
+                simplification_rules: load_simplification_rules(simplify_rules),
+                ..Default::default()
+            };
             if let Ok(lines) = read_lines(name) {
                 for line in lines {
                     if let Ok(ip) = line {
-                        match scala_native_demangle::demangle(&ip, &Default::default()) {
+                        match scala_native_demangle::demangle(&ip, &config) {
                             Ok(res) => println!("{} = {}", ip, res),
                             Err(e) => println!("{} ERROR {}", ip, e),
                         }
@@ -46,19 +78,39 @@ fn main() {
                 }
             }
         }
-        Commands::Id { name, debug } => {
+        Commands::Id {
+            name,
+            debug,
+            simplify_rules,
+        } => {
             println!(
                 "{}",
                 scala_native_demangle::demangle(
                     name,
                     &DemanglingConfig {
                         debug: *debug,
+                        simplification_rules: load_simplification_rules(simplify_rules),
                         ..Default::default()
                     }
                 )
                 .unwrap()
             )
         }
+        Commands::Filter {
+            debug,
+            simplify_rules,
+        } => {
+            let config = DemanglingConfig {
+                debug: *debug,
+                simplification_rules: load_simplification_rules(simplify_rules),
+                ..Default::default()
+            };
+            for line in io::stdin().lock().lines() {
+                if let Ok(line) = line {
+                    println!("{}", scala_native_demangle::demangle_embedded(&line, &config));
+                }
+            }
+        }
     }
 }
 
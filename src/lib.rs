@@ -4,25 +4,89 @@
 //!
 //! 1. Name mangling rules: https://scala-native.org/en/latest/contrib/mangling.html
 //! 2. Scala implementation: https://github.com/indoorvivants/sn-demangler
+//!
+//! [`demangle`] renders the mangled identifier straight to a `String`. For
+//! callers that want to inspect the owner, parameter types or return type of
+//! a symbol programmatically, [`parse`] exposes the same information as a
+//! typed [`DemangledSymbol`] tree instead. [`mangle`] is the inverse: it
+//! turns a [`DemangledSymbol`] back into a `_S…` identifier. [`demangle_embedded`]
+//! demangles `_S…` symbols found anywhere inside a larger piece of text, such
+//! as a stack trace or linker error. How class names get simplified (e.g.
+//! `java.lang.Object` to `Object`) is controlled by
+//! [`DemanglingConfig::simplification_rules`], which can be extended with
+//! project-specific rules via [`SimplificationRules::load_from_file`].
 
-pub type DemangleError = String;
 pub type ParsingResult<T> = Result<T, DemangleError>;
 
+/// A structured parse failure, carrying enough information to point at
+/// exactly where and why parsing went wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemangleError {
+    /// The identifier that was being parsed when this error occurred.
+    pub input: String,
+    /// Byte offset into `input` where parsing failed.
+    pub offset: usize,
+    /// The fragment of `input` starting at `offset` that couldn't be parsed.
+    pub fragment: String,
+    /// The parser frames active when the error occurred, innermost first -
+    /// e.g. `["name", "sig_name", "member_name", "defn_name"]`.
+    pub context: Vec<&'static str>,
+    /// The tags/forms that would have been accepted at this point, e.g.
+    /// `["F", "R", "D", "P", "C", "G", "K", "I"]` for `sig_name`.
+    pub expected: Vec<&'static str>,
+}
+
+impl DemangleError {
+    fn new(offset: usize, fragment: &str, expected: Vec<&'static str>) -> Self {
+        DemangleError {
+            input: String::new(),
+            offset,
+            fragment: fragment.to_string(),
+            context: Vec::new(),
+            expected,
+        }
+    }
+
+    fn frame(mut self, name: &'static str) -> Self {
+        self.context.push(name);
+        self
+    }
+
+    fn with_input(mut self, input: &str) -> Self {
+        self.input = input.to_string();
+        self
+    }
+}
+
+impl std::fmt::Display for DemangleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.input)?;
+        writeln!(f, "{}^", " ".repeat(self.offset))?;
+        write!(f, "unexpected `{}` at byte {}", self.fragment, self.offset)?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected one of: {}", self.expected.join("/"))?;
+        }
+        if !self.context.is_empty() {
+            write!(f, " (while parsing {})", self.context.join(" < "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DemangleError {}
+
 pub struct DemanglingConfig {
     pub collapse_scala_names: bool,
     pub debug: bool,
+    pub simplification_rules: SimplificationRules,
 }
 
-static DEFAULT_CONFIG: DemanglingConfig = DemanglingConfig {
-    collapse_scala_names: true,
-    debug: false,
-};
-
 impl Default for DemanglingConfig {
     fn default() -> Self {
         DemanglingConfig {
             collapse_scala_names: true,
             debug: false,
+            simplification_rules: SimplificationRules::default(),
         }
     }
 }
@@ -40,51 +104,592 @@ impl DemanglingConfig {
     }
 }
 
+/// A single rewrite tried by [`SimplificationRules::apply`] against a
+/// fully-qualified class name, e.g. when rendering `java.lang.Object` as
+/// `Object`. Rules are tried in order; the first one that matches wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimplificationRule {
+    /// Replace the name if it's exactly equal to `from`.
+    Alias { from: String, to: String },
+    /// Strip `prefix` off the front of the name, if present.
+    StripPrefix(String),
+    /// Match against a glob `pattern` containing at most one `*` wildcard,
+    /// e.g. `myapp.*.Foo`. If `to` also contains a `*`, it's replaced with
+    /// whatever the pattern's `*` matched, e.g. pattern `myapp.*.Foo`/`to`
+    /// `*.Foo` turns `myapp.widgets.Foo` into `widgets.Foo`.
+    Glob { pattern: String, to: String },
+}
+
+impl SimplificationRule {
+    fn apply(&self, name: &str) -> Option<String> {
+        match self {
+            SimplificationRule::Alias { from, to } => (name == from).then(|| to.clone()),
+            SimplificationRule::StripPrefix(prefix) => {
+                name.strip_prefix(prefix.as_str()).map(|s| s.to_string())
+            }
+            SimplificationRule::Glob { pattern, to } => {
+                let captured = glob_match(pattern, name)?;
+                Some(match to.find('*') {
+                    Some(star) => format!("{}{}{}", &to[..star], captured, &to[star + 1..]),
+                    None => to.clone(),
+                })
+            }
+        }
+    }
+}
+
+// Matches `name` against a glob `pattern` containing at most one `*`
+// wildcard, returning the substring the wildcard matched (empty if `pattern`
+// has none and matches exactly).
+fn glob_match(pattern: &str, name: &str) -> Option<String> {
+    match pattern.find('*') {
+        Some(star) => {
+            let (prefix, suffix) = (&pattern[..star], &pattern[star + 1..]);
+            if name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+            {
+                Some(name[prefix.len()..name.len() - suffix.len()].to_string())
+            } else {
+                None
+            }
+        }
+        None => (pattern == name).then(String::new),
+    }
+}
+
+/// An ordered, extensible set of [`SimplificationRule`]s applied by
+/// `common_type_name` when `collapse_scala_names` is on. [`Default`] matches
+/// the hard-coded rewrites this crate has always applied; additional rules
+/// can be loaded with [`SimplificationRules::load_from_file`] and are tried
+/// before the defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimplificationRules {
+    pub rules: Vec<SimplificationRule>,
+}
+
+impl Default for SimplificationRules {
+    fn default() -> Self {
+        SimplificationRules {
+            rules: vec![
+                SimplificationRule::Alias {
+                    from: "java.lang.Object".to_string(),
+                    to: "Object".to_string(),
+                },
+                SimplificationRule::Alias {
+                    from: "java.lang.String".to_string(),
+                    to: "String".to_string(),
+                },
+                SimplificationRule::Alias {
+                    from: "java.lang.Throwable".to_string(),
+                    to: "Throwable".to_string(),
+                },
+                SimplificationRule::StripPrefix("scala.collection.immutable.".to_string()),
+            ],
+        }
+    }
+}
+
+impl SimplificationRules {
+    fn apply(&self, name: String) -> String {
+        for rule in &self.rules {
+            if let Some(simplified) = rule.apply(&name) {
+                return simplified;
+            }
+        }
+        name
+    }
+
+    /// Load extra rules from a simple line-based config file, one rule per
+    /// line, tried before the built-in defaults:
+    ///
+    /// ```text
+    /// alias <exact-name> <replacement>
+    /// strip-prefix <prefix>
+    /// glob <pattern-with-at-most-one-*> <replacement>
+    /// ```
+    ///
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn load_from_file(path: &std::path::Path) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut rules = Self::parse(&contents).rules;
+        rules.extend(SimplificationRules::default().rules);
+        Ok(SimplificationRules { rules })
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(3, ' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("alias"), Some(from), Some(to)) => rules.push(SimplificationRule::Alias {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                }),
+                (Some("strip-prefix"), Some(prefix), None) => {
+                    rules.push(SimplificationRule::StripPrefix(prefix.to_string()))
+                }
+                (Some("glob"), Some(pattern), Some(to)) => rules.push(SimplificationRule::Glob {
+                    pattern: pattern.to_string(),
+                    to: to.to_string(),
+                }),
+                _ => {}
+            }
+        }
+        SimplificationRules { rules }
+    }
+}
+
+/// A fully parsed Scala Native symbol.
+///
+/// `Display` reproduces exactly what [`demangle`] would have returned as a
+/// flat `String`, so existing callers can switch to [`parse`] without any
+/// change in output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DemangledSymbol {
+    /// `T <name>` - a top-level name, e.g. a module or object.
+    TopLevel { name: String },
+    /// `M <name> <sig-name>` - a member of `owner`.
+    Member { owner: String, sig: SigName },
+}
+
+impl std::fmt::Display for DemangledSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DemangledSymbol::TopLevel { name } => write!(f, "{name}"),
+            DemangledSymbol::Member { owner, sig } => write!(f, "{owner}.{sig}"),
+        }
+    }
+}
+
+/// The signature of a member, i.e. everything that can follow `M <name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigName {
+    /// `F <name> <scope>`
+    Field { name: String, scope: Scope },
+    /// `R <type-name>+ E`
+    Constructor { params: Vec<TypeName> },
+    /// `D <name> <type-name>+ E <scope>`
+    Method {
+        name: String,
+        params: Vec<TypeName>,
+        return_type: Box<TypeName>,
+        scope: Scope,
+    },
+    /// `P <name> <type-name>+ E`
+    Proxy {
+        name: String,
+        params: Vec<TypeName>,
+        return_type: Box<TypeName>,
+    },
+    /// `C <name>`
+    CExtern { name: String },
+    /// `G <name>`
+    Generated { name: String },
+    /// `K <name> <type-name>+ E` - what follows `K` is read as a plain
+    /// `<name>`; a nested `<sig-name>` there is not parsed.
+    Duplicate {
+        name: String,
+        params: Vec<TypeName>,
+        return_type: Box<TypeName>,
+    },
+    /// `I` - static class initializer.
+    ClassInit,
+}
+
+impl std::fmt::Display for SigName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigName::Field { name, scope } => write!(f, "{scope}{name}"),
+            SigName::Constructor { params } => write!(f, "{}", join_display(", ", params)),
+            SigName::Method {
+                name,
+                params,
+                return_type,
+                scope,
+            } => render_callable(f, &scope.to_string(), name, params, return_type),
+            SigName::Proxy {
+                name,
+                params,
+                return_type,
+            } => {
+                // NB: mirrors a long-standing quirk of the flat-string demangler,
+                // which drops the last parameter before the return type here.
+                let shown = &params[..params.len().saturating_sub(1)];
+                render_callable(f, "", name, shown, return_type)
+            }
+            SigName::CExtern { name } | SigName::Generated { name } => write!(f, "{name}"),
+            SigName::Duplicate {
+                name,
+                params,
+                return_type,
+            } => {
+                // NB: mirrors a long-standing quirk of the flat-string demangler,
+                // which drops the last parameter before the return type here.
+                let shown = &params[..params.len().saturating_sub(1)];
+                render_callable(f, "", name, shown, return_type)
+            }
+            SigName::ClassInit => write!(f, "<clinit>"),
+        }
+    }
+}
+
+fn render_callable(
+    f: &mut std::fmt::Formatter<'_>,
+    prefix: &str,
+    name: &str,
+    params: &[TypeName],
+    return_type: &TypeName,
+) -> std::fmt::Result {
+    if params.is_empty() {
+        write!(f, "{prefix}{name}: {return_type}")
+    } else {
+        write!(f, "{prefix}{name}({}): {return_type}", join_display(",", params))
+    }
+}
+
+fn join_display<T: std::fmt::Display>(sep: &str, items: &[T]) -> String {
+    items
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+/// `<scope> ::= P <defn-name> | p <defn-name> | O | o`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    Public,
+    PublicStatic,
+    Private(Box<DemangledSymbol>),
+    PrivateStatic(Box<DemangledSymbol>),
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Public | Scope::PublicStatic => Ok(()),
+            Scope::Private(owner) | Scope::PrivateStatic(owner) => {
+                write!(f, "<private[{owner}]>")
+            }
+        }
+    }
+}
+
+/// A Scala Native primitive type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimitiveKind {
+    Boolean,
+    Char,
+    Float,
+    Double,
+    Unit,
+    Null,
+    Nothing,
+    Byte,
+    Short,
+    Int,
+    Long,
+}
+
+impl PrimitiveKind {
+    fn bare_name(&self) -> &'static str {
+        match self {
+            PrimitiveKind::Boolean => "Boolean",
+            PrimitiveKind::Char => "Char",
+            PrimitiveKind::Float => "Float",
+            PrimitiveKind::Double => "Double",
+            PrimitiveKind::Unit => "Unit",
+            PrimitiveKind::Null => "Null",
+            PrimitiveKind::Nothing => "Nothing",
+            PrimitiveKind::Byte => "Byte",
+            PrimitiveKind::Short => "Short",
+            PrimitiveKind::Int => "Int",
+            PrimitiveKind::Long => "Long",
+        }
+    }
+}
+
+/// A primitive type-name. `name` is `scala.<Name>` if `collapse_scala_names`
+/// was off at parse time, else plain `<Name>`; `display` is `name` with
+/// [`DemanglingConfig::simplification_rules`] applied (or `name` unchanged
+/// if `collapse_scala_names` was off), baked in at parse
+/// time (like [`TypeName::NullableClass`]) since `Display` can't take a
+/// runtime config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Primitive {
+    pub kind: PrimitiveKind,
+    pub name: String,
+    pub display: String,
+}
+
+impl std::fmt::Display for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display)
+    }
+}
+
+/// `<type-name>` - see the grammar notes on [`type_name`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeName {
+    Primitive(Primitive),
+    /// `v` - c vararg.
+    Vararg,
+    /// `R _` - c pointer.
+    Pointer,
+    /// `R <type-name>+ E` - c function pointer.
+    CFunction(Vec<TypeName>),
+    /// `S <type-name>+ E` - c anonymous struct.
+    CStruct(Vec<TypeName>),
+    /// `A <type-name> <number> _` - c array of `<number>` elements.
+    CArray(Box<TypeName>, usize),
+    /// `L A <type-name> _` - nullable array.
+    Array(Box<TypeName>),
+    /// A nullable class type-name. `name` is the raw, fully-qualified class
+    /// name as it appears in the mangled symbol; `display` is the same name
+    /// with [`DemanglingConfig::simplification_rules`] applied (or unchanged
+    /// if `collapse_scala_names` was off), baked in at
+    /// parse time like [`Primitive::display`] since `Display` can't take a
+    /// runtime config.
+    NullableClass { name: String, display: String },
+    /// `X <name>` - nonnull exact class type-name.
+    ExactClass(String),
+}
+
+impl std::fmt::Display for TypeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeName::Primitive(p) => write!(f, "{p}"),
+            TypeName::Vararg => write!(f, "<c vararg>"),
+            TypeName::Pointer => write!(f, "<c pointer>"),
+            TypeName::CFunction(params) => write!(f, "CFunction[{}]", join_display(",", params)),
+            TypeName::CStruct(params) => write!(f, "CStruct[{}]", join_display(",", params)),
+            TypeName::CArray(inner, size) => write!(f, "CArray[{inner};{size}]"),
+            TypeName::Array(inner) => write!(f, "Array[{inner}]"),
+            TypeName::NullableClass { display, .. } => write!(f, "{display}"),
+            TypeName::ExactClass(name) => write!(f, "{name}"),
+        }
+    }
+}
+
 pub fn demangle(input: &str, config: &DemanglingConfig) -> ParsingResult<String> {
+    parse(input, config).map(|(_, sym)| sym.to_string())
+}
+
+pub fn demangle_with_defaults(input: &str) -> ParsingResult<String> {
+    return demangle(input, &DemanglingConfig::default());
+}
+
+/// Scan `text` for `_S…`-mangled identifiers and replace each one with its
+/// demangled form, passing everything else through untouched. Unlike
+/// [`demangle`], this doesn't require the whole input to be a single mangled
+/// identifier - it works on stack traces, linker errors, `nm`/`objdump`
+/// output, or any other text a mangled symbol might be embedded in.
+pub fn demangle_embedded(text: &str, config: &DemanglingConfig) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("_S") {
+            if let Ok((consumed, sym)) = parse(&text[i..], config) {
+                output.push_str(&sym.to_string());
+                i += consumed;
+                continue;
+            }
+        }
+        let ch = text[i..].chars().next().unwrap();
+        output.push(ch);
+        i += ch.len_utf8();
+    }
+    output
+}
+
+/// Parse a mangled Scala Native identifier into a structured [`DemangledSymbol`],
+/// instead of flattening it straight to a `String` like [`demangle`] does.
+///
+/// Returns the number of bytes of `input` that were consumed, so callers
+/// (such as [`demangle_embedded`]) can resume past exactly the identifier
+/// that was parsed, even if `input` has trailing text that isn't part of it.
+pub fn parse(input: &str, config: &DemanglingConfig) -> ParsingResult<(usize, DemangledSymbol)> {
     if !input.starts_with("_S") {
-        return Err("identifier doesn't start with _S".to_string());
+        let fragment = input.get(0..2.min(input.len())).unwrap_or(input);
+        return Err(DemangleError::new(0, fragment, vec!["_S"]).with_input(input));
     } else {
         config.log_name("demangle", &input[2..]);
-        return defn_name(&input[2..], config);
+        let (consumed, sym) = defn_name(&input[2..], 2, config).map_err(|e| e.with_input(input))?;
+        return Ok((2 + consumed, sym));
     }
 }
 
-pub fn demangle_with_defaults(input: &str) -> ParsingResult<String> {
-    return demangle(input, &DEFAULT_CONFIG);
+/// Re-emit a [`DemangledSymbol`] as a mangled `_S…` identifier.
+///
+/// This is the inverse of [`parse`]: `parse(&mangle(sym), config)` returns
+/// `sym` back (alongside the length of the mangled string, since `sym` is
+/// the whole of it) when `config` is the config `sym` was parsed with. The
+/// mangled form doesn't encode any of [`DemanglingConfig`]'s display
+/// choices, so [`Primitive`]'s `name`/`display` and
+/// [`TypeName::NullableClass`]'s `display` are recomputed from `config`.
+pub fn mangle(sym: &DemangledSymbol) -> String {
+    format!("_S{}", mangle_defn(sym))
+}
+
+fn mangle_defn(sym: &DemangledSymbol) -> String {
+    match sym {
+        DemangledSymbol::TopLevel { name } => format!("T{}", mangle_name(name)),
+        DemangledSymbol::Member { owner, sig } => {
+            format!("M{}{}", mangle_name(owner), mangle_sig(sig))
+        }
+    }
+}
+
+fn mangle_sig(sig: &SigName) -> String {
+    match sig {
+        SigName::Field { name, scope } => {
+            format!("F{}{}", mangle_name(name), mangle_scope(scope))
+        }
+        SigName::Constructor { params } => format!("R{}E", mangle_type_names(params)),
+        SigName::Method {
+            name,
+            params,
+            return_type,
+            scope,
+        } => format!(
+            "D{}{}{}E{}",
+            mangle_name(name),
+            mangle_type_names(params),
+            mangle_type_name(return_type),
+            mangle_scope(scope)
+        ),
+        SigName::Proxy {
+            name,
+            params,
+            return_type,
+        } => format!(
+            "P{}{}{}E",
+            mangle_name(name),
+            mangle_type_names(params),
+            mangle_type_name(return_type)
+        ),
+        SigName::CExtern { name } => format!("C{}", mangle_name(name)),
+        SigName::Generated { name } => format!("G{}", mangle_name(name)),
+        SigName::Duplicate {
+            name,
+            params,
+            return_type,
+        } => format!(
+            "K{}{}{}E",
+            mangle_name(name),
+            mangle_type_names(params),
+            mangle_type_name(return_type)
+        ),
+        SigName::ClassInit => "IE".to_string(),
+    }
+}
+
+fn mangle_scope(scope: &Scope) -> String {
+    match scope {
+        Scope::Public => "O".to_string(),
+        Scope::PublicStatic => "o".to_string(),
+        Scope::Private(owner) => format!("P{}", mangle_defn(owner)),
+        Scope::PrivateStatic(owner) => format!("p{}", mangle_defn(owner)),
+    }
+}
+
+fn mangle_type_names(params: &[TypeName]) -> String {
+    params.iter().map(mangle_type_name).collect::<String>()
+}
+
+fn mangle_type_name(t: &TypeName) -> String {
+    match t {
+        TypeName::Primitive(p) => mangle_primitive(p.kind).to_string(),
+        TypeName::Vararg => "v".to_string(),
+        TypeName::Pointer => "R_".to_string(),
+        TypeName::CFunction(params) => format!("R{}E", mangle_type_names(params)),
+        TypeName::CStruct(params) => format!("S{}E", mangle_type_names(params)),
+        TypeName::CArray(inner, size) => format!("A{}{size}_", mangle_type_name(inner)),
+        TypeName::Array(inner) => format!("LA{}_", mangle_type_name(inner)),
+        TypeName::NullableClass { name, .. } => format!("L{}", mangle_name(name)),
+        TypeName::ExactClass(name) => format!("X{}", mangle_name(name)),
+    }
+}
+
+fn mangle_primitive(kind: PrimitiveKind) -> &'static str {
+    match kind {
+        PrimitiveKind::Byte => "b",
+        PrimitiveKind::Short => "s",
+        PrimitiveKind::Int => "i",
+        PrimitiveKind::Long => "j",
+        PrimitiveKind::Boolean => "z",
+        PrimitiveKind::Char => "c",
+        PrimitiveKind::Float => "f",
+        PrimitiveKind::Double => "d",
+        PrimitiveKind::Unit => "u",
+        PrimitiveKind::Null => "l",
+        PrimitiveKind::Nothing => "n",
+    }
+}
+
+// <name> is length-prefixed; if the name itself starts with a digit or a
+// `-`, a `-` is inserted after the length so the parser doesn't mistake the
+// name's leading digits for more of the length, or its leading `-` for the
+// escape.
+fn mangle_name(s: &str) -> String {
+    let len = s.len();
+    if s.chars().next().is_some_and(|c| c.is_ascii_digit() || c == '-') {
+        format!("{len}-{s}")
+    } else {
+        format!("{len}{s}")
+    }
 }
 
 // private sub parsers
 // <defn-name> ::=
 //     T <name>                       // top-level name
 //     M <name> <sig-name>            // member name
-fn defn_name(input: &str, config: &DemanglingConfig) -> ParsingResult<String> {
+fn defn_name(
+    input: &str,
+    offset: usize,
+    config: &DemanglingConfig,
+) -> ParsingResult<(usize, DemangledSymbol)> {
     config.log_name("defn_name", input);
     if input.starts_with("T") {
-        return toplevel_name(&input[1..], config);
+        let (consumed, sym) = toplevel_name(&input[1..], offset + 1, config)
+            .map_err(|e| e.frame("defn_name"))?;
+        return Ok((1 + consumed, sym));
     } else if input.starts_with("M") {
-        return member_name(&input[1..], config);
+        let (consumed, sym) =
+            member_name(&input[1..], offset + 1, config).map_err(|e| e.frame("defn_name"))?;
+        return Ok((1 + consumed, sym));
     } else {
-        if input.len() > 0 {
-            return Err(format!(
-                "defn_name: unknown name modifier '{}'",
-                input[0..0].to_string()
-            ));
-        } else {
-            return Err("defn_name: unexpectedly empty rest of identifier".to_string());
-        }
+        let fragment = input.get(0..1).unwrap_or("");
+        return Err(DemangleError::new(offset, fragment, vec!["T", "M"]).frame("defn_name"));
     }
 }
 
-fn toplevel_name(input: &str, config: &DemanglingConfig) -> ParsingResult<String> {
+fn toplevel_name(
+    input: &str,
+    offset: usize,
+    config: &DemanglingConfig,
+) -> ParsingResult<(usize, DemangledSymbol)> {
     config.log_name("toplevel_name", input);
-    return name(input, config).map(|t| t.1);
+    return name(input, offset, config)
+        .map(|(consumed, nm)| (consumed, DemangledSymbol::TopLevel { name: nm }))
+        .map_err(|e| e.frame("toplevel_name"));
 }
-fn member_name(input: &str, config: &DemanglingConfig) -> ParsingResult<String> {
+fn member_name(
+    input: &str,
+    offset: usize,
+    config: &DemanglingConfig,
+) -> ParsingResult<(usize, DemangledSymbol)> {
     config.log_name("member_name", input);
-    let (consumed, owner) = name(input, config)?;
-    let signature = sig_name(&input[consumed..], config);
+    let (consumed, owner) = name(input, offset, config).map_err(|e| e.frame("member_name"))?;
+    let (sig_consumed, sig) = sig_name(&input[consumed..], offset + consumed, config)
+        .map_err(|e| e.frame("member_name"))?;
 
-    return signature.and_then(|s| return Ok(format!("{}.{}", owner, s)));
+    return Ok((consumed + sig_consumed, DemangledSymbol::Member { owner, sig }));
 }
 
 // <sig-name> ::=
@@ -95,99 +700,131 @@ fn member_name(input: &str, config: &DemanglingConfig) -> ParsingResult<String>
 //     C <name>                            // c extern name
 //     G <name>                            // generated name
 //     K <sig-name> <type-name>+ E         // duplicate name
-fn sig_name(input: &str, config: &DemanglingConfig) -> ParsingResult<String> {
+const SIG_NAME_TAGS: [&str; 8] = ["F", "R", "D", "P", "C", "G", "K", "I"];
+
+fn sig_name(input: &str, offset: usize, config: &DemanglingConfig) -> ParsingResult<(usize, SigName)> {
     config.log_name("sig_name", input);
-    if input.starts_with("C") || input.starts_with("G") {
-        return Ok(name(&input[1..], config)?.1);
+    if input.starts_with("C") {
+        let (consumed, nm) = name(&input[1..], offset + 1, config).map_err(|e| e.frame("sig_name"))?;
+        return Ok((1 + consumed, SigName::CExtern { name: nm }));
+    } else if input.starts_with("G") {
+        let (consumed, nm) = name(&input[1..], offset + 1, config).map_err(|e| e.frame("sig_name"))?;
+        return Ok((1 + consumed, SigName::Generated { name: nm }));
     } else if input.starts_with("I") {
-        return Ok("<clinit>".to_string());
+        // class initializers are commonly (but not necessarily) followed by
+        // a stray `E`, mirroring the terminator on the other sig kinds
+        let consumed = if input[1..].starts_with("E") { 2 } else { 1 };
+        return Ok((consumed, SigName::ClassInit));
     } else if input.starts_with("F") {
-        let (consumed, field_name) = name(&input[1..], config)?;
-        // return field_name.and_then(|nm| {
+        let (consumed, field_name) =
+            name(&input[1..], offset + 1, config).map_err(|e| e.frame("sig_name"))?;
         let rest = &input[(1 + consumed)..];
-        return scope(rest, config).map(|sc| format!("{}{}", render_scope(sc), field_name));
-        // });
+        let (scope_consumed, sc) =
+            scope(rest, offset + 1 + consumed, config).map_err(|e| e.frame("sig_name"))?;
+        return Ok((
+            1 + consumed + scope_consumed,
+            SigName::Field {
+                name: field_name,
+                scope: sc,
+            },
+        ));
     } else if input.starts_with("R") {
-        let type_names = read_type_names(&input[1..], config)?;
-        return Ok(type_names.1.join(", "));
+        let (types_consumed, type_names) =
+            read_type_names(&input[1..], offset + 1, config).map_err(|e| e.frame("sig_name"))?;
+        return Ok((
+            1 + types_consumed + 1, /* "E" terminator */
+            SigName::Constructor { params: type_names },
+        ));
     } else if input.starts_with("K") {
-        // TODO: basically the same as D case below
         let after_tag = &input[1..];
-        let (consumed, nm) = name(after_tag, config)?;
+        let (consumed, nm) = name(after_tag, offset + 1, config).map_err(|e| e.frame("sig_name"))?;
 
         let after_name = &after_tag[consumed..];
-        let (_, type_names) = read_type_names(after_name, config)?;
-
-        let signature = match type_names.len() {
-            1 => format!("{}: {}", nm, type_names.join(",")),
-            n => format!(
-                "{}({}): {}",
-                nm,
-                type_names[0..n - 2].join(","),
-                type_names.get(n - 1).unwrap_or(&"???".to_string())
-            ),
-        };
+        let (types_consumed, type_names) = read_type_names(after_name, offset + 1 + consumed, config)
+            .map_err(|e| e.frame("sig_name"))?;
+        let (params, return_type) = split_params_and_return(type_names);
 
-        return Ok(signature);
+        return Ok((
+            1 + consumed + types_consumed + 1,
+            SigName::Duplicate {
+                name: nm,
+                params,
+                return_type: Box::new(return_type),
+            },
+        ));
     } else if input.starts_with("P") {
-        // TODO: basically the same as D case below
         let after_tag = &input[1..];
-        let (consumed, nm) = name(after_tag, config)?;
+        let (consumed, nm) = name(after_tag, offset + 1, config).map_err(|e| e.frame("sig_name"))?;
 
         let after_name = &after_tag[consumed..];
-        let (_, type_names) = read_type_names(after_name, config)?;
-
-        let signature = match type_names.len() {
-            1 => format!("{}: {}", nm, type_names.join(",")),
-            n => format!(
-                "{}({}): {}",
-                nm,
-                type_names[0..n - 2].join(","),
-                type_names.get(n - 1).unwrap_or(&"???".to_string())
-            ),
-        };
+        let (types_consumed, type_names) = read_type_names(after_name, offset + 1 + consumed, config)
+            .map_err(|e| e.frame("sig_name"))?;
+        let (params, return_type) = split_params_and_return(type_names);
 
-        return Ok(signature);
+        return Ok((
+            1 + consumed + types_consumed + 1,
+            SigName::Proxy {
+                name: nm,
+                params,
+                return_type: Box::new(return_type),
+            },
+        ));
     } else if input.starts_with("D") {
         let after_tag = &input[1..];
-        let (consumed, nm) = name(after_tag, config)?;
+        let (consumed, nm) = name(after_tag, offset + 1, config).map_err(|e| e.frame("sig_name"))?;
 
         let after_name = &after_tag[consumed..];
-        let (consumed, type_names) = read_type_names(after_name, config)?;
+        let (types_consumed, type_names) = read_type_names(after_name, offset + 1 + consumed, config)
+            .map_err(|e| e.frame("sig_name"))?;
 
-        let after_types = &after_name[consumed + 1..];
+        let after_types = &after_name[types_consumed + 1..];
         config.log_name(
             "sig_name:D",
             format!("type_names: {type_names:?}, after: {after_types}").as_str(),
         );
-        let sc = scope(&after_types, config)?;
-
-        let signature = match type_names.len() {
-            1 => format!("{}{}: {}", render_scope(sc), nm, type_names.join(",")),
-            n => format!(
-                "{}{}({}): {}",
-                render_scope(sc),
-                nm,
-                type_names[0..n - 1].join(","),
-                type_names.get(n - 1).unwrap_or(&"???".to_string())
-            ),
-        };
+        let (scope_consumed, sc) = scope(
+            &after_types,
+            offset + 1 + consumed + types_consumed + 1,
+            config,
+        )
+        .map_err(|e| e.frame("sig_name"))?;
+        let (params, return_type) = split_params_and_return(type_names);
 
-        return Ok(signature);
+        return Ok((
+            1 + consumed + types_consumed + 1 + scope_consumed,
+            SigName::Method {
+                name: nm,
+                params,
+                return_type: Box::new(return_type),
+                scope: sc,
+            },
+        ));
     } else {
-        return Err(format!(
-            "sig_name: expected to start with F/R/D/P/C/G/K/I, {}",
-            &input
-        )
-        .to_string());
+        let fragment = input.get(0..1).unwrap_or("");
+        return Err(DemangleError::new(offset, fragment, SIG_NAME_TAGS.to_vec()).frame("sig_name"));
     }
 }
 
-fn read_type_names(input: &str, config: &DemanglingConfig) -> ParsingResult<(usize, Vec<String>)> {
+// <type-name>+ in the grammar always ends with the return type, so the last
+// element read by `read_type_names` is the return type and everything before
+// it are the parameters.
+fn split_params_and_return(mut type_names: Vec<TypeName>) -> (Vec<TypeName>, TypeName) {
+    match type_names.pop() {
+        Some(return_type) => (type_names, return_type),
+        None => (Vec::new(), TypeName::ExactClass("???".to_string())),
+    }
+}
+
+fn read_type_names(
+    input: &str,
+    offset: usize,
+    config: &DemanglingConfig,
+) -> ParsingResult<(usize, Vec<TypeName>)> {
     let mut pos = 0;
     let mut result = Vec::new();
     while !input[pos..].starts_with("E") {
-        let (consumed, nm) = type_name(&input[pos..], config)?;
+        let (consumed, nm) =
+            type_name(&input[pos..], offset + pos, config).map_err(|e| e.frame("read_type_names"))?;
         result.push(nm);
         pos += consumed;
     }
@@ -195,32 +832,21 @@ fn read_type_names(input: &str, config: &DemanglingConfig) -> ParsingResult<(usi
     return Ok((pos, result));
 }
 
-fn scala_root_name(name: &str, config: &DemanglingConfig) -> String {
+fn common_type_name(name: String, config: &DemanglingConfig) -> String {
     if !config.collapse_scala_names {
-        return format!("scala.{name}");
+        return name;
     } else {
-        return name.to_string();
-    };
+        return config.simplification_rules.apply(name);
+    }
 }
 
-fn common_type_name(name: String, config: &DemanglingConfig) -> String {
+// Qualifies a primitive's bare name with the `scala.` root package, unless
+// `collapse_scala_names` is on.
+fn scala_root_name(name: &str, config: &DemanglingConfig) -> String {
     if !config.collapse_scala_names {
-        return name;
+        format!("scala.{name}")
     } else {
-        let immut = "scala.collection.immutable.";
-
-        if name == "java.lang.Object" {
-            return "Object".to_string();
-        } else if name == "java.lang.String" {
-            return "String".to_string();
-        } else if name == "java.lang.Throwable" {
-            return "Throwable".to_string();
-        } else if name.starts_with(immut) {
-            return name.strip_prefix(immut).unwrap_or(&name).to_string();
-            // return "Throwable".to_string();
-        } else {
-            return name;
-        }
+        name.to_string()
     }
 }
 
@@ -247,117 +873,140 @@ fn common_type_name(name: String, config: &DemanglingConfig) -> String {
 //     A <type-name> _                // nonnull array type-name
 //     X <name>                       // nonnull exact class type-name
 //     <name>                         // nonnull class type-name
-fn type_name(input: &str, config: &DemanglingConfig) -> ParsingResult<(usize, String)> {
+fn type_name(input: &str, offset: usize, config: &DemanglingConfig) -> ParsingResult<(usize, TypeName)> {
     let mut chars = input.chars();
     config.log(format!("type_name: {input}").as_str());
 
-    let scala_root_namer = |name: &str| scala_root_name(name, config);
-    let common_type_namer = |name: String| common_type_name(name, config);
-
-    let result = match chars.next() {
-        Some('v') => Ok((1, "<c vararg>".to_string())),
-        Some('z') => Ok((1, scala_root_namer("Boolean"))),
-        Some('c') => Ok((1, scala_root_namer("Char"))),
-        Some('f') => Ok((1, scala_root_namer("Float"))),
-        Some('d') => Ok((1, scala_root_namer("Double"))),
-        Some('u') => Ok((1, scala_root_namer("Unit"))),
-        Some('l') => Ok((1, scala_root_namer("Null"))),
-        Some('n') => Ok((1, scala_root_namer("Nothing"))),
-        Some('b') => Ok((1, scala_root_namer("Byte"))),
-        Some('s') => Ok((1, scala_root_namer("Short"))),
-        Some('i') => Ok((1, scala_root_namer("Int"))),
-        Some('j') => Ok((1, scala_root_namer("Long"))),
-
-        Some('R') => match chars.next() {
-            Some('_') => Ok((2, "<c pointer>".to_string())),
-            Some(c) => Err(format!("type_name: after R expected _, got `{c}` instead").to_string()),
-            None => Err("type_name: unexpected end of input".to_string()),
-        },
+    let primitive = |kind: PrimitiveKind| {
+        let name = scala_root_name(kind.bare_name(), config);
+        let display = common_type_name(name.clone(), config);
+        TypeName::Primitive(Primitive { kind, name, display })
+    };
+
+    return match chars.next() {
+        Some('v') => Ok((1, TypeName::Vararg)),
+        Some('z') => Ok((1, primitive(PrimitiveKind::Boolean))),
+        Some('c') => Ok((1, primitive(PrimitiveKind::Char))),
+        Some('f') => Ok((1, primitive(PrimitiveKind::Float))),
+        Some('d') => Ok((1, primitive(PrimitiveKind::Double))),
+        Some('u') => Ok((1, primitive(PrimitiveKind::Unit))),
+        Some('l') => Ok((1, primitive(PrimitiveKind::Null))),
+        Some('n') => Ok((1, primitive(PrimitiveKind::Nothing))),
+        Some('b') => Ok((1, primitive(PrimitiveKind::Byte))),
+        Some('s') => Ok((1, primitive(PrimitiveKind::Short))),
+        Some('i') => Ok((1, primitive(PrimitiveKind::Int))),
+        Some('j') => Ok((1, primitive(PrimitiveKind::Long))),
+
+        Some('R') => {
+            if input[1..].starts_with('_') {
+                Ok((2, TypeName::Pointer))
+            } else {
+                let (consumed, params) = read_type_names(&input[1..], offset + 1, config)
+                    .map_err(|e| e.frame("type_name"))?;
+                Ok((consumed + 2, TypeName::CFunction(params)))
+            }
+        }
+        Some('S') => {
+            let (consumed, params) = read_type_names(&input[1..], offset + 1, config)
+                .map_err(|e| e.frame("type_name"))?;
+            Ok((consumed + 2, TypeName::CStruct(params)))
+        }
         Some('L') => {
-            let (consumed, type_name) = nullable_type_name(&input[1..], config)?;
-            Ok((consumed + 1, common_type_namer(type_name)))
+            let (consumed, type_name) = nullable_type_name(&input[1..], offset + 1, config)
+                .map_err(|e| e.frame("type_name"))?;
+            Ok((consumed + 1, type_name))
         }
         Some('A') => {
-            let (consumed, tn) = type_name(&input[1..], config)?;
+            let (consumed, tn) = type_name(&input[1..], offset + 1, config)
+                .map_err(|e| e.frame("type_name"))?;
             let after_type_name = &input[1 + consumed..];
-            let num = number(after_type_name);
+            let num_len = number(after_type_name);
+            let num = &after_type_name[..num_len];
+            let size = num.parse::<usize>().map_err(|_| {
+                DemangleError::new(offset + 1 + consumed, num, vec!["<number>"]).frame("type_name")
+            })?;
             Ok((
-                consumed + num + 1, /* "_" at the end */
-                format!("CArray[{}]", tn),
+                consumed + num_len + 2, /* "A" tag byte + "_" at the end */
+                TypeName::CArray(Box::new(tn), size),
             ))
         }
         Some('X') => {
-            let (consumed, class_type_name) = name(&input[1..], config)?;
-            Ok((consumed + 1, class_type_name))
+            let (consumed, class_type_name) = name(&input[1..], offset + 1, config)
+                .map_err(|e| e.frame("type_name"))?;
+            Ok((consumed + 1, TypeName::ExactClass(class_type_name)))
         }
-        Some(other) => Err(format!("type_name: unexpected start character `{other}`").to_string()),
-        None => Err("type_name: unexpected end of input".to_string()),
+        Some(_) => Err(DemangleError::new(
+            offset,
+            input.get(0..1).unwrap_or(""),
+            vec!["v", "z", "c", "f", "d", "u", "l", "n", "b", "s", "i", "j", "R", "S", "L", "A", "X"],
+        )
+        .frame("type_name")),
+        None => Err(DemangleError::new(offset, "", vec!["<type-name>"]).frame("type_name")),
     };
-
-    return result;
 }
 
 fn number(input: &str) -> usize {
     return input.chars().take_while(|c| c.is_digit(10)).count();
 }
 
-fn nullable_type_name(input: &str, config: &DemanglingConfig) -> ParsingResult<(usize, String)> {
+fn nullable_type_name(
+    input: &str,
+    offset: usize,
+    config: &DemanglingConfig,
+) -> ParsingResult<(usize, TypeName)> {
     let mut chars = input.chars();
 
     match chars.next() {
         Some('A') => {
-            let (consumed, ar) = type_name(&input[1..], config)?;
-            return Ok((consumed + 2, format!("Array[{}]", ar)));
+            let (consumed, ar) =
+                type_name(&input[1..], offset + 1, config).map_err(|e| e.frame("nullable_type_name"))?;
+            return Ok((consumed + 2, TypeName::Array(Box::new(ar))));
         }
         Some('X') => {
-            let (consumed, n) = name(input, config)?;
+            let (consumed, n) =
+                name(&input[1..], offset + 1, config).map_err(|e| e.frame("nullable_type_name"))?;
 
-            return Ok((consumed + 1, n));
+            return Ok((consumed + 1, TypeName::ExactClass(n)));
         }
         Some(d) if d.is_digit(10) => {
-            return name(input, config);
+            let (consumed, n) = name(input, offset, config).map_err(|e| e.frame("nullable_type_name"))?;
+            let display = common_type_name(n.clone(), config);
+            return Ok((consumed, TypeName::NullableClass { name: n, display }));
+        }
+        Some(_) => {
+            let fragment = input.get(0..1).unwrap_or("");
+            return Err(
+                DemangleError::new(offset, fragment, vec!["A", "X", "<digit>"]).frame("nullable_type_name")
+            );
+        }
+        None => {
+            return Err(DemangleError::new(offset, "", vec!["A", "X", "<digit>"]).frame("nullable_type_name"))
         }
-        Some(a) => return Err(format!("nullable_type_name: unexpected start `{a}`")),
-        None => return Err("nullable_type_name: unexpected end of input".to_string()),
-    };
-}
-
-enum Scope {
-    Public,
-    PublicStatic,
-    Private(String),
-    PrivateStatic(String),
-}
-
-fn render_scope(scope: Scope) -> String {
-    return match scope {
-        Scope::Public => "".to_string(),
-        Scope::PublicStatic => "".to_string(),
-        Scope::Private(inn) => format!("<private[{}]>", inn),
-        Scope::PrivateStatic(inn) => format!("<private[{}]>", inn),
     };
 }
 
 // <scope> ::=
 //     P <defn-name>                  // private to defn-name
 //     O                              // public
-fn scope(input: &str, config: &DemanglingConfig) -> Result<Scope, String> {
+fn scope(input: &str, offset: usize, config: &DemanglingConfig) -> ParsingResult<(usize, Scope)> {
     config.log_name("scope", input);
     if input.starts_with("O") {
-        return Ok(Scope::Public);
+        return Ok((1, Scope::Public));
     } else if input.starts_with("o") {
-        return Ok(Scope::PublicStatic);
+        return Ok((1, Scope::PublicStatic));
     } else if input.starts_with("P") {
-        return defn_name(&input[1..], config).map(|i| return Scope::Private(i));
+        let (consumed, sym) = defn_name(&input[1..], offset + 1, config).map_err(|e| e.frame("scope"))?;
+        return Ok((1 + consumed, Scope::Private(Box::new(sym))));
     } else if input.starts_with("p") {
-        return defn_name(&input[1..], config).map(|i| return Scope::PrivateStatic(i));
+        let (consumed, sym) = defn_name(&input[1..], offset + 1, config).map_err(|e| e.frame("scope"))?;
+        return Ok((1 + consumed, Scope::PrivateStatic(Box::new(sym))));
     } else {
-        return Err(format!("scope: cannot read `{}`", input).to_string());
+        let fragment = input.get(0..1).unwrap_or("");
+        return Err(DemangleError::new(offset, fragment, vec!["O", "o", "P", "p"]).frame("scope"));
     }
 }
 
-fn name(input: &str, config: &DemanglingConfig) -> ParsingResult<(usize, String)> {
-    //println!("name: {}", input);
+fn name(input: &str, offset: usize, config: &DemanglingConfig) -> ParsingResult<(usize, String)> {
     config.log_name("name", input);
     let mut number_end: usize = 0;
     for c in input.chars() {
@@ -372,24 +1021,49 @@ fn name(input: &str, config: &DemanglingConfig) -> ParsingResult<(usize, String)
 
         match usize::from_str_radix(length, 10) {
             Ok(res) => {
-                if rest.starts_with("-") {
-                    return Ok((length.len() + 1 + res, rest[1..(1 + res)].to_string()));
+                let (prefix_len, body) = if rest.starts_with("-") {
+                    (1, rest.get(1..).unwrap_or(""))
                 } else {
-                    return Ok((length.len() + res, rest[0..res].to_string()));
-                }
+                    (0, rest)
+                };
+                return match body.get(0..res) {
+                    Some(s) => Ok((length.len() + prefix_len + res, s.to_string())),
+                    None => Err(
+                        DemangleError::new(offset, length, vec!["<length>"]).frame("name")
+                    ),
+                };
             }
             Err(_) => {
-                return Err("name: invalid length".to_string());
+                return Err(
+                    DemangleError::new(offset, length, vec!["<length>"]).frame("name")
+                );
             }
         }
     } else {
-        return Err(format!("name: invalid input `{}`", input.to_string()));
+        let fragment = input.get(0..1).unwrap_or("");
+        return Err(DemangleError::new(offset, fragment, vec!["<digit>"]).frame("name"));
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::demangle;
+    use crate::{
+        demangle, demangle_embedded, mangle, parse, DemangledSymbol, DemanglingConfig, Primitive,
+        PrimitiveKind, Scope, SigName, SimplificationRule, SimplificationRules, TypeName,
+    };
+
+    const FIXTURES: &[&str] = &[
+        "_ST10__dispatch",
+        "_SM42sttp.model.headers.CacheDirective$MinFreshD12productArityiEO",
+        "_SM42scala.scalanative.runtime.SymbolFormatter$D10inBounds$1L32scala.scalanative.unsigned.ULongizEPT42scala.scalanative.runtime.SymbolFormatter$",
+        "_SM41scalaboot.template.scalatemplate$package$D10$anonfun$3L26scalaboot.template.ContextL15scala.Function1L23java.lang.StringBuilderL31scalaboot.template.UnsafeCursorL23scalaboot.template.MoveuEPT41scalaboot.template.scalatemplate$package$",
+        "_SM33scala.scalanative.unsafe.package$D11fromCStringL28scala.scalanative.unsafe.PtrL24java.nio.charset.CharsetL16java.lang.StringEO",
+        "_SM17java.lang.IntegerD7compareiiiEo",
+        "_SM38scala.scalanative.junit.JUnitFrameworkIE",
+        "_SM10fansi.TrieD17$init$$$anonfun$5cLAL10fansi.Trie_L12scala.Tuple2uEpT10fansi.Trie",
+        "_SM3FooP3bariiiE",
+        "_SM3fooRAi3_E",
+    ];
 
     fn run(s: &str) -> String {
         return demangle(s, &Default::default()).unwrap();
@@ -418,16 +1092,16 @@ mod tests {
             "sttp.model.headers.CacheDirective$MinFresh.productArity: Int"
         );
 
-        assert_eq!(run_raw("_SM42scala.scalanative.runtime.SymbolFormatter$D10inBounds$1L32scala.scalanative.unsigned.ULongizEPT42scala.scalanative.runtime.SymbolFormatter$"), 
+        assert_eq!(run_raw("_SM42scala.scalanative.runtime.SymbolFormatter$D10inBounds$1L32scala.scalanative.unsigned.ULongizEPT42scala.scalanative.runtime.SymbolFormatter$"),
             "scala.scalanative.runtime.SymbolFormatter$.<private[scala.scalanative.runtime.SymbolFormatter$]>inBounds$1(scala.scalanative.unsigned.ULong,scala.Int): scala.Boolean");
 
-        assert_eq!(run("_SM42scala.scalanative.runtime.SymbolFormatter$D10inBounds$1L32scala.scalanative.unsigned.ULongizEPT42scala.scalanative.runtime.SymbolFormatter$"), 
+        assert_eq!(run("_SM42scala.scalanative.runtime.SymbolFormatter$D10inBounds$1L32scala.scalanative.unsigned.ULongizEPT42scala.scalanative.runtime.SymbolFormatter$"),
             "scala.scalanative.runtime.SymbolFormatter$.<private[scala.scalanative.runtime.SymbolFormatter$]>inBounds$1(scala.scalanative.unsigned.ULong,Int): Boolean");
 
-        assert_eq!(run_raw("_SM41scalaboot.template.scalatemplate$package$D10$anonfun$3L26scalaboot.template.ContextL15scala.Function1L23java.lang.StringBuilderL31scalaboot.template.UnsafeCursorL23scalaboot.template.MoveuEPT41scalaboot.template.scalatemplate$package$"), 
+        assert_eq!(run_raw("_SM41scalaboot.template.scalatemplate$package$D10$anonfun$3L26scalaboot.template.ContextL15scala.Function1L23java.lang.StringBuilderL31scalaboot.template.UnsafeCursorL23scalaboot.template.MoveuEPT41scalaboot.template.scalatemplate$package$"),
             "scalaboot.template.scalatemplate$package$.<private[scalaboot.template.scalatemplate$package$]>$anonfun$3(scalaboot.template.Context,scala.Function1,java.lang.StringBuilder,scalaboot.template.UnsafeCursor,scalaboot.template.Move): scala.Unit");
 
-        assert_eq!(run("_SM41scalaboot.template.scalatemplate$package$D10$anonfun$3L26scalaboot.template.ContextL15scala.Function1L23java.lang.StringBuilderL31scalaboot.template.UnsafeCursorL23scalaboot.template.MoveuEPT41scalaboot.template.scalatemplate$package$"), 
+        assert_eq!(run("_SM41scalaboot.template.scalatemplate$package$D10$anonfun$3L26scalaboot.template.ContextL15scala.Function1L23java.lang.StringBuilderL31scalaboot.template.UnsafeCursorL23scalaboot.template.MoveuEPT41scalaboot.template.scalatemplate$package$"),
             "scalaboot.template.scalatemplate$package$.<private[scalaboot.template.scalatemplate$package$]>$anonfun$3(scalaboot.template.Context,scala.Function1,java.lang.StringBuilder,scalaboot.template.UnsafeCursor,scalaboot.template.Move): Unit");
 
         assert_eq!(run("_SM33scala.scalanative.unsafe.package$D11fromCStringL28scala.scalanative.unsafe.PtrL24java.nio.charset.CharsetL16java.lang.StringEO"), "scala.scalanative.unsafe.package$.fromCString(scala.scalanative.unsafe.Ptr,java.nio.charset.Charset): String");
@@ -442,6 +1116,183 @@ mod tests {
             "scala.scalanative.junit.JUnitFramework.<clinit>"
         );
 
-        assert_eq!(run("_SM10fansi.TrieD17$init$$$anonfun$5cLAL10fansi.Trie_L12scala.Tuple2uEpT10fansi.Trie"), "fansi.Trie.<private[fansi.Trie]>$init$$$anonfun$5(Char,Array[fansi.Trie],scala.Tuple2): Unit")
+        assert_eq!(run("_SM10fansi.TrieD17$init$$$anonfun$5cLAL10fansi.Trie_L12scala.Tuple2uEpT10fansi.Trie"), "fansi.Trie.<private[fansi.Trie]>$init$$$anonfun$5(Char,Array[fansi.Trie],scala.Tuple2): Unit");
+
+        // proxy names share the Duplicate sig's quirk of dropping the last
+        // parameter before the return type.
+        assert_eq!(run("_SM3FooP3bariiiE"), "Foo.bar(Int): Int");
+
+        assert_eq!(run("_SM3fooRAi3_E"), "foo.CArray[Int;3]");
+    }
+
+    #[test]
+    fn mangle_round_trips_through_parse() {
+        for fixture in FIXTURES {
+            for config in [
+                crate::DemanglingConfig::default(),
+                crate::DemanglingConfig {
+                    collapse_scala_names: false,
+                    ..Default::default()
+                },
+            ] {
+                let (consumed, sym) = parse(fixture, &config).unwrap();
+                assert_eq!(consumed, fixture.len());
+                let remangled = mangle(&sym);
+                let (_, reparsed) = parse(&remangled, &config).unwrap();
+                assert_eq!(reparsed, sym, "round trip failed for {fixture}");
+            }
+        }
+    }
+
+    #[test]
+    fn demangle_embedded_leaves_surrounding_text_untouched() {
+        let text = "undefined symbol: _SM17java.lang.IntegerD7compareiiiEo at offset 0x10";
+        assert_eq!(
+            demangle_embedded(text, &Default::default()),
+            "undefined symbol: java.lang.Integer.compare(Int,Int): Int at offset 0x10"
+        );
+        assert_eq!(
+            demangle_embedded("no symbols here", &Default::default()),
+            "no symbols here"
+        );
+    }
+
+    #[test]
+    fn demangle_embedded_skips_oversized_length_prefix_instead_of_panicking() {
+        let text = "log line _ST99999999x more text";
+        assert_eq!(demangle_embedded(text, &Default::default()), text);
+    }
+
+    #[test]
+    fn structured_output_matches_display() {
+        let mangled =
+            "_SM17java.lang.IntegerD7compareiiiEo";
+        let (consumed, symbol) = parse(mangled, &Default::default()).unwrap();
+        assert_eq!(consumed, mangled.len());
+
+        match &symbol {
+            DemangledSymbol::Member { owner, sig } => {
+                assert_eq!(owner, "java.lang.Integer");
+                assert_eq!(sig.to_string(), "compare(Int,Int): Int");
+            }
+            DemangledSymbol::TopLevel { .. } => panic!("expected a member symbol"),
+        }
+
+        assert_eq!(symbol.to_string(), run(mangled));
+    }
+
+    #[test]
+    fn nullable_class_keeps_the_raw_fully_qualified_name() {
+        let mangled = "_SM33scala.scalanative.unsafe.package$D11fromCStringL28scala.scalanative.unsafe.PtrL24java.nio.charset.CharsetL16java.lang.StringEO";
+        let (_, symbol) = parse(mangled, &Default::default()).unwrap();
+
+        match &symbol {
+            DemangledSymbol::Member { sig, .. } => match sig {
+                SigName::Method { return_type, .. } => match return_type.as_ref() {
+                    TypeName::NullableClass { name, display } => {
+                        assert_eq!(name, "java.lang.String");
+                        assert_eq!(display, "String");
+                    }
+                    other => panic!("expected a NullableClass return type, got {other:?}"),
+                },
+                other => panic!("expected a Method sig, got {other:?}"),
+            },
+            DemangledSymbol::TopLevel { .. } => panic!("expected a member symbol"),
+        }
+    }
+
+    #[test]
+    fn parse_error_points_at_the_failing_byte() {
+        let mangled = "_SM17java.lang.IntegerZ7compareiiiEo";
+        let err = parse(mangled, &Default::default()).unwrap_err();
+
+        assert_eq!(err.offset, 22);
+        assert_eq!(err.fragment, "Z");
+        assert_eq!(err.expected, crate::SIG_NAME_TAGS.to_vec());
+        assert_eq!(err.context, vec!["sig_name", "member_name", "defn_name"]);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{mangled}\n{}^\nunexpected `Z` at byte 22, expected one of: F/R/D/P/C/G/K/I (while parsing sig_name < member_name < defn_name)",
+                " ".repeat(22)
+            )
+        );
+    }
+
+    #[test]
+    fn nested_type_name_error_gets_a_frame_per_nesting_level() {
+        let mangled = "_SM1aD1bS1QEEO";
+        let err = parse(mangled, &Default::default()).unwrap_err();
+
+        assert_eq!(err.offset, 9);
+        assert_eq!(err.fragment, "1");
+        assert_eq!(
+            err.context,
+            vec![
+                "type_name",
+                "read_type_names",
+                "type_name",
+                "read_type_names",
+                "sig_name",
+                "member_name",
+                "defn_name",
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_simplification_rules_apply_before_the_defaults() {
+        let mut rules = SimplificationRules::default();
+        rules.rules.insert(
+            0,
+            SimplificationRule::StripPrefix("com.example.myapp.".to_string()),
+        );
+        let config = DemanglingConfig {
+            simplification_rules: rules,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            demangle(
+                "_SM24com.example.myapp.WidgetD6toNameL25com.example.myapp.OptionsL16java.lang.StringEO",
+                &config
+            )
+            .unwrap(),
+            "com.example.myapp.Widget.toName(Options): String"
+        );
+    }
+
+    #[test]
+    fn cfunction_type_name_round_trips() {
+        let sym = DemangledSymbol::Member {
+            owner: "Foo".to_string(),
+            sig: SigName::Method {
+                name: "baz".to_string(),
+                params: vec![TypeName::CFunction(vec![TypeName::Primitive(Primitive {
+                    kind: PrimitiveKind::Int,
+                    name: "Int".to_string(),
+                    display: "Int".to_string(),
+                })])],
+                return_type: Box::new(TypeName::Primitive(Primitive {
+                    kind: PrimitiveKind::Unit,
+                    name: "Unit".to_string(),
+                    display: "Unit".to_string(),
+                })),
+                scope: Scope::Public,
+            },
+        };
+
+        let mangled = mangle(&sym);
+        let (consumed, reparsed) = parse(&mangled, &Default::default()).unwrap();
+        assert_eq!(consumed, mangled.len());
+        assert_eq!(reparsed, sym);
+    }
+
+    #[test]
+    fn non_nullable_c_array_type_name_consumes_the_whole_input() {
+        // `A <type-name> <number> _` - a constructor taking one CArray[Int; 3] param.
+        let mangled = "_SM3fooRAi3_E";
+        let (consumed, _) = parse(mangled, &Default::default()).unwrap();
+        assert_eq!(consumed, mangled.len());
     }
 }